@@ -0,0 +1,227 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::thread;
+
+use notify::{RecursiveMode, Watcher, RecommendedWatcher};
+use serde::{Deserialize, Serialize};
+
+/**
+    Every tunable of the simulation, loaded from a TOML file passed on the command line.
+    Values default to whatever the hardcoded `const`s used to be, so an empty/missing
+    config file still produces the original behaviour.
+**/
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The number of units between the two nearest edges of the window
+    pub scale: f32,
+    /// The coordinates of the point at the center of the window
+    pub dx: f32,
+    pub dy: f32,
+
+    /// The maximum time that a particle may live for, in frames
+    pub particle_lifetime: f32,
+    /// The speed of the simulation: higher is faster but less accurate
+    pub epsilon: f32,
+    /// The number of substeps to the simulation: does not affect particle speed but directly affects simulation accuracy and efficiency
+    pub substeps: usize,
+    /// How long it takes for a particle to fade in (fed into a sigmoid function, so at this many frames it'll have ~46% alpha)
+    pub particle_fade_in: f32,
+    /// How long it takes for a particle to fade out before it dies
+    pub particle_fade_out: f32,
+
+    /// The number of initial particles
+    pub initial_particles: usize,
+    /// The number of particles to spawn each frame
+    pub particles_per_frame: u32,
+
+    /// Whether to draw circles around the particle head and tail, very expensive
+    pub round_particles: bool,
+    /// Diameter of a particle, in pixels
+    pub particle_size: f32,
+
+    /// If true, only one update step will be done for each frame (bypassing tetra's physics/rendering separation)
+    pub animation_mode: bool,
+    /// If true, frames will be saved to the disk
+    pub saving: bool,
+
+    /// The width of the window
+    pub width: u32,
+    /// The height of the window
+    pub height: u32,
+
+    /// The number of threads to run the simulation on
+    pub threads: u32,
+    /// The number of particles for each "task batch"
+    pub task_size: usize,
+    /// Set to 1 for infinite animation, set to some other value for a looping animation
+    pub loop_frames: u32,
+
+    /// The threshold above which a pixel starts contributing to the bloom pass
+    pub bloom_threshold: f32,
+    /// The exposure fed into the tonemap pass: higher brightens the bloom before the Reinhard
+    /// compression, pushing more of it towards white; lower gives a dimmer result
+    pub bloom_intensity: f32,
+    /// Multiplies the blur's sample radius; higher gives a wider, softer glow
+    pub bloom_blur_radius: f32,
+    /// How many horizontal+vertical blur passes to ping-pong through; higher gives a wider glow at the cost of performance
+    pub bloom_passes: u32,
+
+    /// An expression (in `z` and `t`) overriding the built-in field function; empty to use the built-in series
+    pub expression: String,
+    /// A second expression to morph towards and back over one `loop_frames` cycle; empty to disable morphing
+    pub expression_b: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            scale: 5.0,
+            dx: -3.75,
+            dy: 0.0,
+
+            particle_lifetime: 160.0,
+            epsilon: 0.01,
+            substeps: 6,
+            particle_fade_in: 6.0,
+            particle_fade_out: 6.0,
+
+            initial_particles: 40000,
+            particles_per_frame: 1000,
+
+            round_particles: false,
+            particle_size: 2.0,
+
+            animation_mode: false,
+            saving: false,
+
+            width: 1920,
+            height: 1080,
+
+            threads: 8,
+            task_size: 512,
+            loop_frames: 1,
+
+            bloom_threshold: 0.3,
+            bloom_intensity: 1.0,
+            bloom_blur_radius: 1.0,
+            bloom_passes: 1,
+
+            expression: String::new(),
+            expression_b: String::new(),
+        }
+    }
+}
+
+impl Config {
+    /**
+        Loads a `Config` from a TOML file. Falls back to `Config::default()` (logging a
+        warning) if the file is missing or fails to parse, so a bad/absent config never
+        stops the program from running. Semantically-invalid values (e.g. `threads: 0`)
+        are clamped by `sanitize` rather than rejected outright.
+    **/
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let mut config = match std::fs::read_to_string(path) {
+            Ok(contents) => match toml::from_str(&contents) {
+                Ok(config) => config,
+                Err(err) => {
+                    eprintln!("Couldn't parse config file {}: {}", path.display(), err);
+                    Self::default()
+                }
+            },
+            Err(err) => {
+                eprintln!("Couldn't read config file {}: {}", path.display(), err);
+                Self::default()
+            }
+        };
+        config.sanitize();
+        config
+    }
+
+    /**
+        Clamps fields that would otherwise crash the renderer (division by zero, an
+        empty thread pool, ...) to the smallest valid value, so a bad but parseable
+        config can't bring the program down.
+    **/
+    fn sanitize(&mut self) {
+        if self.threads < 1 {
+            eprintln!("Config: threads must be at least 1, clamping {} to 1", self.threads);
+            self.threads = 1;
+        }
+        if self.task_size < 1 {
+            eprintln!("Config: task_size must be at least 1, clamping {} to 1", self.task_size);
+            self.task_size = 1;
+        }
+        if self.width < 1 {
+            eprintln!("Config: width must be at least 1, clamping {} to 1", self.width);
+            self.width = 1;
+        }
+        if self.height < 1 {
+            eprintln!("Config: height must be at least 1, clamping {} to 1", self.height);
+            self.height = 1;
+        }
+    }
+}
+
+/**
+    Watches `path` on a background thread and reloads `config` in place whenever the file
+    changes on disk, so tweaking the TOML takes effect on the next frame without a restart.
+
+    Watches the parent directory rather than `path` itself: many editors (vim, and most
+    "safe save" implementations) save by writing a temp file and renaming it over the
+    original, which replaces the inode and can silently stop a direct file watch from
+    firing again afterwards.
+**/
+pub fn watch(path: impl Into<PathBuf>, config: Arc<Mutex<Config>>) {
+    let path = path.into();
+    // Event paths are absolute, so make `path` absolute too or it would never match them
+    let path = if path.is_absolute() {
+        path
+    } else {
+        std::env::current_dir().map(|cwd| cwd.join(&path)).unwrap_or(path)
+    };
+    thread::spawn(move || {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher: RecommendedWatcher = match notify::recommended_watcher(tx) {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                eprintln!("Couldn't start config watcher: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            eprintln!("Couldn't watch config directory {}: {}", dir.display(), err);
+            return;
+        }
+
+        for event in rx {
+            match event {
+                Ok(event) if event.paths.iter().any(|p| p == &path) => {
+                    let mut reloaded = Config::load_from_file(&path);
+                    match config.lock() {
+                        Ok(mut lock) => {
+                            // The camera (dx/dy/scale) is also driven live by mouse pan/zoom;
+                            // carry it across the reload instead of snapping it back to
+                            // whatever's saved on disk.
+                            reloaded.dx = lock.dx;
+                            reloaded.dy = lock.dy;
+                            reloaded.scale = lock.scale;
+                            *lock = reloaded;
+                        }
+                        Err(err) => panic!("Couldn't lock config! {}", err),
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => eprintln!("Config watcher error: {}", err),
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    });
+}
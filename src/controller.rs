@@ -0,0 +1,42 @@
+/**
+    Decouples frame advancement from rendering: the simulation can be suspended,
+    resumed, or stepped a fixed number of iterations, independently of how often
+    `draw` is called.
+**/
+#[derive(Clone, Copy, Debug)]
+pub struct SimController {
+    paused: bool,
+    steps_remaining: u32,
+}
+
+impl SimController {
+    pub fn new() -> Self {
+        Self { paused: false, steps_remaining: 0 }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Queues up `n` forced advances, even while paused
+    pub fn step(&mut self, n: u32) {
+        self.steps_remaining += n;
+    }
+
+    /**
+        Whether the simulation should advance this tick. Consumes one queued step if
+        any are pending, otherwise advances only when not paused.
+    **/
+    pub fn should_advance(&mut self) -> bool {
+        if self.steps_remaining > 0 {
+            self.steps_remaining -= 1;
+            true
+        } else {
+            !self.paused
+        }
+    }
+}
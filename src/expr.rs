@@ -0,0 +1,265 @@
+use crate::Complex;
+
+/**
+    A parsed complex-valued expression over the variables `z` (the current particle
+    position) and `t` (the current frame index), as written by the user in the config
+    file (e.g. `"z^3 - 1"`, `"sin(z) + t*i"`).
+
+    Evaluating an `Expr` is cheap: it's a plain tree walk with no allocation, so it's
+    safe to call from the hot substep loop in `update_particles`.
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Z,
+    T,
+    I,
+    Const(f32),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    Exp(Box<Expr>),
+    Sin(Box<Expr>),
+    Cos(Box<Expr>),
+    Conj(Box<Expr>),
+    Abs(Box<Expr>),
+}
+
+impl Expr {
+    /**
+        Evaluates the expression for a given frame index `t` and particle position `z`.
+    **/
+    pub fn eval(&self, t: f32, z: Complex) -> Complex {
+        match self {
+            Expr::Z => z,
+            Expr::T => Complex::new(t, 0.0),
+            Expr::I => Complex::new(0.0, 1.0),
+            Expr::Const(x) => Complex::new(*x, 0.0),
+            Expr::Add(a, b) => a.eval(t, z) + b.eval(t, z),
+            Expr::Sub(a, b) => a.eval(t, z) - b.eval(t, z),
+            Expr::Mul(a, b) => a.eval(t, z) * b.eval(t, z),
+            Expr::Div(a, b) => a.eval(t, z) / b.eval(t, z),
+            Expr::Pow(a, b) => a.eval(t, z).powc(b.eval(t, z)),
+            Expr::Neg(a) => -a.eval(t, z),
+            Expr::Exp(a) => a.eval(t, z).exp(),
+            Expr::Sin(a) => a.eval(t, z).sin(),
+            Expr::Cos(a) => a.eval(t, z).cos(),
+            Expr::Conj(a) => a.eval(t, z).conj(),
+            Expr::Abs(a) => Complex::new(a.eval(t, z).norm(), 0.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnexpectedEnd,
+    UnexpectedToken,
+    UnknownFunction,
+}
+
+/**
+    Parses a user-supplied expression string into an `Expr` tree.
+
+    Grammar (lowest to highest precedence): `+ -`, then `* /`, then unary `-`, then `^`
+    (right-associative), then atoms (numbers, `z`, `t`, `i`, `name(expr)`, `(expr)`).
+**/
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let tokens = tokenize(input);
+    let mut parser = Parser { tokens: &tokens, pos: 0 };
+    let expr = parser.parse_add_sub()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(ParseError::UnexpectedToken);
+    }
+    Ok(expr)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f32),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut chars = input.chars().peekable();
+    let mut tokens = Vec::new();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' | '\n' | '\r' => { chars.next(); }
+            '+' => { tokens.push(Token::Plus); chars.next(); }
+            '-' => { tokens.push(Token::Minus); chars.next(); }
+            '*' => { tokens.push(Token::Star); chars.next(); }
+            '/' => { tokens.push(Token::Slash); chars.next(); }
+            '^' => { tokens.push(Token::Caret); chars.next(); }
+            '(' => { tokens.push(Token::LParen); chars.next(); }
+            ')' => { tokens.push(Token::RParen); chars.next(); }
+            '0'..='9' | '.' => {
+                let mut number = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_digit() || c == '.' {
+                        number.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Number(number.parse().unwrap_or(0.0)));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            _ => { chars.next(); }
+        }
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_add_sub(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_mul_div()?;
+        loop {
+            match self.peek() {
+                Some(Token::Plus) => {
+                    self.pos += 1;
+                    lhs = Expr::Add(Box::new(lhs), Box::new(self.parse_mul_div()?));
+                }
+                Some(Token::Minus) => {
+                    self.pos += 1;
+                    lhs = Expr::Sub(Box::new(lhs), Box::new(self.parse_mul_div()?));
+                }
+                _ => break,
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn parse_mul_div(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Star) => {
+                    self.pos += 1;
+                    lhs = Expr::Mul(Box::new(lhs), Box::new(self.parse_unary()?));
+                }
+                Some(Token::Slash) => {
+                    self.pos += 1;
+                    lhs = Expr::Div(Box::new(lhs), Box::new(self.parse_unary()?));
+                }
+                _ => break,
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        if let Some(Token::Minus) = self.peek() {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.parse_unary()?)));
+        }
+        self.parse_pow()
+    }
+
+    fn parse_pow(&mut self) -> Result<Expr, ParseError> {
+        let base = self.parse_atom()?;
+        if let Some(Token::Caret) = self.peek() {
+            self.pos += 1;
+            let exponent = self.parse_unary()?; // right-associative
+            return Ok(Expr::Pow(Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr, ParseError> {
+        match self.peek().cloned() {
+            Some(Token::Number(n)) => { self.pos += 1; Ok(Expr::Const(n)) }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let inner = self.parse_add_sub()?;
+                match self.peek() {
+                    Some(Token::RParen) => { self.pos += 1; Ok(inner) }
+                    _ => Err(ParseError::UnexpectedToken),
+                }
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                match name.as_str() {
+                    "z" => Ok(Expr::Z),
+                    "t" => Ok(Expr::T),
+                    "i" => Ok(Expr::I),
+                    _ => {
+                        if let Some(Token::LParen) = self.peek() {
+                            self.pos += 1;
+                            let arg = self.parse_add_sub()?;
+                            match self.peek() {
+                                Some(Token::RParen) => self.pos += 1,
+                                _ => return Err(ParseError::UnexpectedToken),
+                            }
+                            let arg = Box::new(arg);
+                            match name.as_str() {
+                                "exp" => Ok(Expr::Exp(arg)),
+                                "sin" => Ok(Expr::Sin(arg)),
+                                "cos" => Ok(Expr::Cos(arg)),
+                                "conj" => Ok(Expr::Conj(arg)),
+                                "abs" => Ok(Expr::Abs(arg)),
+                                _ => Err(ParseError::UnknownFunction),
+                            }
+                        } else {
+                            Err(ParseError::UnknownFunction)
+                        }
+                    }
+                }
+            }
+            Some(_) => Err(ParseError::UnexpectedToken),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_polynomial() {
+        let expr = parse("z^3 - 1").unwrap();
+        assert_eq!(expr.eval(0.0, Complex::new(2.0, 0.0)), Complex::new(7.0, 0.0));
+    }
+
+    #[test]
+    fn parses_function_calls_and_i() {
+        let expr = parse("sin(z) + t*i").unwrap();
+        let got = expr.eval(3.0, Complex::new(0.0, 0.0));
+        assert_eq!(got, Complex::new(0.0, 3.0));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse("z ++ )").is_err());
+    }
+}
@@ -1,57 +1,21 @@
+mod config;
+mod controller;
+mod expr;
+
 use image::RgbaImage;
 use rand::prelude::*;
 use tetra::{Context, ContextBuilder, State};
 use tetra::graphics::{self, Canvas, Color, DrawParams, Shader, ImageData};
 use tetra::graphics::mesh::{Mesh, GeometryBuilder, ShapeStyle};
+use tetra::input::{self, Key, MouseButton};
 use tetra::math::Vec2;
 use scoped_threadpool::Pool;
 use std::sync::mpsc::{Receiver, Sender, self};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::thread;
 
-// The number of units between the two nearest edges of the window
-const SCALE: f32 = 5.0;
-// The coordinates of the point at the center of the window
-const DX: f32 = -3.75;
-const DY: f32 = 0.0;
-
-// The maximum time that a particle may live for, in frames
-const PARTICLE_LIFETIME: f32 = 160.0;
-// The speed of the simulation: higher is faster but less accurate
-const EPSILON: f32 = 0.01;
-// The number of substeps to the simulation: does not affect particle speed but directly affects simulation accuracy and efficiency
-const SUBSTEPS: usize = 6;
-// How long it takes for a particle to fade in (fed into a sigmoid function, so at PARTICLE_FADE_IN frames it'll have ~46% alpha)
-const PARTICLE_FADE_IN: f32 = 6.0;
-// How long it takes for a particle to fade out before it dies
-const PARTICLE_FADE_OUT: f32 = 6.0;
-
-// The number of initial particles
-const INITIAL_PARTICLES: usize = 40000;
-// The number of particles to spawn each frame
-const PARTICLES_PER_FRAME: u32 = 1000;
-
-// Whether to draw circles around the particle head and tail, very expensive
-const ROUND_PARTICLES: bool = false;
-// Diameter of a particle, in pixels
-const PARTICLE_SIZE: f32 = 2.0;
-
-// If true, only one update step will be done for each frame (bypassing tetra's physics/rendering separation)
-const ANIMATION_MODE: bool = false;
-// If true, frames will be saved to the disk
-const SAVING: bool = false;
-
-// The width of the window
-const WIDTH: u32 = 1920;
-// The height of the window
-const HEIGHT: u32 = 1080;
-
-// The number of threads to run the simulation on
-const THREADS: u32 = 8;
-// The number of particles for each "task batch"
-const TASK_SIZE: usize = 512;
-// Set to 1 for infinite animation, set to some other value for a looping animation
-const LOOP_FRAMES: u32 = 1;
+use config::Config;
+use controller::SimController;
 
 type Complex = num::complex::Complex<f32>;
 
@@ -65,6 +29,66 @@ fn f(_t: usize, mut x: Complex) -> Complex {
     x
 }
 
+/**
+    Evaluates the vector field at a given frame/position, using the user-supplied
+    expression when one parsed successfully and falling back to the built-in series `f`
+    otherwise.
+**/
+fn eval_field(t: usize, z: Complex, parsed: Option<&expr::Expr>) -> Complex {
+    match parsed {
+        Some(parsed) => parsed.eval(t as f32, z),
+        None => f(t, z),
+    }
+}
+
+/**
+    The ease used to morph from `f_a` to `f_b` and back over one `loop_frames` cycle:
+    `0` at the start and end of the loop, `1` at the midpoint.
+**/
+fn blend_factor(t: usize, loop_frames: u32) -> f32 {
+    if loop_frames <= 1 {
+        return 0.0;
+    }
+    let t = (t as u32 % loop_frames) as f32;
+    0.5 - 0.5 * (2.0 * std::f32::consts::PI * t / loop_frames as f32).cos()
+}
+
+/**
+    Evaluates `f_a`, morphing towards `f_b` when one is configured, so a looping render
+    can depart from one field and seamlessly return to it by the end of the loop.
+**/
+fn eval_morphed_field(t: usize, z: Complex, expr_a: Option<&expr::Expr>, expr_b: Option<&expr::Expr>, loop_frames: u32) -> Complex {
+    let a = eval_field(t, z, expr_a);
+    match expr_b {
+        Some(_) => {
+            let s = blend_factor(t, loop_frames);
+            let b = eval_field(t, z, expr_b);
+            a * (1.0 - s) + b * s
+        }
+        None => a,
+    }
+}
+
+/**
+    Parses `source` into an expression tree, returning it alongside the source string so
+    callers can detect when it needs to be reparsed. An empty string or a parse failure
+    both fall back to `None`, which makes `eval_field` use the built-in series.
+**/
+fn parse_expr(source: &str) -> (String, Option<expr::Expr>) {
+    let parsed = if source.trim().is_empty() {
+        None
+    } else {
+        match expr::parse(source) {
+            Ok(parsed) => Some(parsed),
+            Err(err) => {
+                eprintln!("Couldn't parse expression {:?}: {:?}, falling back to the built-in field", source, err);
+                None
+            }
+        }
+    };
+    (source.to_string(), parsed)
+}
+
 /**
     Sigmoid function, mapped to [-1, 1]
 **/
@@ -73,11 +97,81 @@ fn sigmoid(x: f32) -> f32 {
 }
 
 /**
-    A single particle: it has a color, stores its current position and the position at its last render, alongside its lifetime and age.
+    How a particle moves, on top of the field advection every particle shares.
 **/
 #[derive(Clone, Copy, Debug)]
+enum ParticleKind {
+    /// Plain normalized-field advection, the original behaviour
+    Streamline,
+    /// Advection plus a constant complex bias added every substep
+    Drift { bias: Complex },
+    /// Advection plus a small RNG-driven perturbation every substep
+    Diffuse { jitter: f32 },
+}
+
+impl ParticleKind {
+    /// Picks a kind at random, weighted so `Streamline` stays the common case
+    fn random(r: &mut impl Rng) -> Self {
+        let roll = r.gen::<f32>();
+        if roll < 0.7 {
+            ParticleKind::Streamline
+        } else if roll < 0.85 {
+            // A bias comparable in magnitude to the unit-vector advection term, so it reads
+            // as a bias on top of the field rather than replacing it
+            let angle = r.gen::<f32>() * std::f32::consts::TAU;
+            let magnitude = 0.3 + r.gen::<f32>() * 0.7;
+            ParticleKind::Drift { bias: Complex::new(magnitude * angle.cos(), magnitude * angle.sin()) }
+        } else {
+            ParticleKind::Diffuse { jitter: 0.05 + r.gen::<f32>() * 0.05 }
+        }
+    }
+
+    /// The color ramp a particle of this kind is sampled from over its lifetime, shaped by the field value `p` at its spawn position
+    fn ramp(&self, r: &mut impl Rng, p: Complex) -> Vec<Color> {
+        match self {
+            ParticleKind::Streamline => vec![
+                Color::rgb(0.8 + 0.2 * r.gen::<f32>() * sigmoid(p.norm()), 0.45 + 0.2 * r.gen::<f32>() * sigmoid(-p.im), 0.23),
+                Color::rgb(0.08, 0.085, 0.12),
+            ],
+            ParticleKind::Drift { .. } => vec![
+                Color::rgb(0.2, 0.5 + 0.2 * r.gen::<f32>() * sigmoid(p.norm()), 0.8 + 0.2 * r.gen::<f32>()),
+                Color::rgb(0.08, 0.12, 0.14),
+            ],
+            ParticleKind::Diffuse { .. } => vec![
+                Color::rgb(0.7 + 0.2 * r.gen::<f32>(), 0.2, 0.6 + 0.2 * r.gen::<f32>() * sigmoid(-p.im)),
+                Color::rgb(0.12, 0.08, 0.14),
+            ],
+        }
+    }
+}
+
+/**
+    Samples a color ramp at `t` (in `[0, 1]`), linearly interpolating between its two
+    nearest stops.
+**/
+fn sample_ramp(ramp: &[Color], t: f32) -> Color {
+    if ramp.len() == 1 {
+        return ramp[0];
+    }
+    let t = t.clamp(0.0, 1.0) * (ramp.len() - 1) as f32;
+    let i = (t as usize).min(ramp.len() - 2);
+    let local_t = t - i as f32;
+    let a = ramp[i];
+    let b = ramp[i + 1];
+    Color::rgb(
+        a.r + (b.r - a.r) * local_t,
+        a.g + (b.g - a.g) * local_t,
+        a.b + (b.b - a.b) * local_t,
+    )
+}
+
+/**
+    A single particle: it has a kind and a color ramp sampled by age, stores its current position and the position at its last render, alongside its lifetime and age.
+**/
+#[derive(Clone, Debug)]
 struct Particle {
-    color: Color,
+    kind: ParticleKind,
+    ramp: Vec<Color>,
     position: Complex,
     old_position: Complex,
     lifetime: f32,
@@ -90,24 +184,23 @@ impl Particle {
         Creates a new particle from the given timestep and particle ID.
         These parameters are then used to randomly generate the particle's parameters.
     **/
-    fn new(mut t: u32, n: u32) -> Self {
-        if LOOP_FRAMES > 1 {
-            t %= LOOP_FRAMES;
+    fn new(mut t: u32, n: u32, config: &Config, field_expr: (Option<&expr::Expr>, Option<&expr::Expr>)) -> Self {
+        if config.loop_frames > 1 {
+            t %= config.loop_frames;
         }
         let seed: u64 = (((t as u64) << 32) | n as u64) ^ 0xCBF52D44320FD62A; // Append t to n and XOR them with a "nothing up my sleeve" number
         let mut r = rand::rngs::StdRng::seed_from_u64(seed);
         let position = Complex::new(
-            (r.gen::<f32>() * 3.0 - 1.5) * SCALE * WIDTH.max(HEIGHT) as f32 / WIDTH as f32 + DX,
-            (r.gen::<f32>() * 3.0 - 1.5) * SCALE * WIDTH.max(HEIGHT) as f32 / HEIGHT as f32 + DY
+            (r.gen::<f32>() * 3.0 - 1.5) * config.scale * config.width.max(config.height) as f32 / config.width as f32 + config.dx,
+            (r.gen::<f32>() * 3.0 - 1.5) * config.scale * config.width.max(config.height) as f32 / config.height as f32 + config.dy
         );
-        let p = f(t as usize, position);
-        let mut color = Color::rgb(0.8 + 0.2 * r.gen::<f32>() * sigmoid(p.norm()), 0.45 + 0.2 * r.gen::<f32>() * sigmoid(-p.im), 0.23);
-        if r.gen::<f32>() < 0.3 {
-            color = Color::rgb(0.08, 0.085, 0.12);
-        }
-        let lifetime = r.gen::<f32>() * PARTICLE_LIFETIME;
+        let p = eval_morphed_field(t as usize, position, field_expr.0, field_expr.1, config.loop_frames);
+        let kind = ParticleKind::random(&mut r);
+        let ramp = kind.ramp(&mut r, p);
+        let lifetime = r.gen::<f32>() * config.particle_lifetime;
         Self {
-            color,
+            kind,
+            ramp,
             old_position: position.clone(),
             position,
             lifetime,
@@ -129,25 +222,97 @@ struct VectorFieldState {
     canvas: Canvas,
     canvas_blur: Canvas,
     canvas_bloom: Canvas,
+    canvas_tonemap: Canvas,
     shader_blur: Shader,
     shader_bloom: Shader,
+    shader_tonemap: Shader,
 
     image_tx: Sender<ImageData>,
+
+    config: Arc<Mutex<Config>>,
+    /// The expression tree parsed from `config.expression` (`f_a`), recomputed only when that string changes
+    cached_expr: (String, Option<expr::Expr>),
+    /// The expression tree parsed from `config.expression_b` (`f_b`, morph target), recomputed only when that string changes
+    cached_expr_b: (String, Option<expr::Expr>),
+
+    controller: SimController,
+    last_mouse: Option<Vec2<f32>>,
 }
 
 impl VectorFieldState {
-    fn new(ctx: &mut Context, image_tx: Sender<ImageData>) -> Self {
+    fn new(ctx: &mut Context, image_tx: Sender<ImageData>, config: Arc<Mutex<Config>>) -> Self {
+        let snapshot = config.lock().unwrap().clone();
+        let cached_expr = parse_expr(&snapshot.expression);
+        let cached_expr_b = parse_expr(&snapshot.expression_b);
         Self {
-            particles: (0..INITIAL_PARTICLES).map(|n| Particle::new(0, n as u32)).collect(),
+            particles: (0..snapshot.initial_particles).map(|n| Particle::new(0, n as u32, &snapshot, (cached_expr.1.as_ref(), cached_expr_b.1.as_ref()))).collect(),
             circle: None,
             t: 0,
-            canvas: Canvas::new(ctx, WIDTH as i32, HEIGHT as i32).unwrap(),
-            canvas_blur: Canvas::new(ctx, WIDTH as i32, HEIGHT as i32).unwrap(),
-            canvas_bloom: Canvas::new(ctx, WIDTH as i32, HEIGHT as i32).unwrap(),
+            canvas: Canvas::new(ctx, snapshot.width as i32, snapshot.height as i32).unwrap(),
+            canvas_blur: Canvas::new(ctx, snapshot.width as i32, snapshot.height as i32).unwrap(),
+            canvas_bloom: Canvas::new(ctx, snapshot.width as i32, snapshot.height as i32).unwrap(),
+            canvas_tonemap: Canvas::new(ctx, snapshot.width as i32, snapshot.height as i32).unwrap(),
             shader_blur: Shader::from_fragment_file(ctx, "shader/blur.frag").unwrap(),
             shader_bloom: Shader::from_fragment_file(ctx, "shader/bloom.frag").unwrap(),
+            shader_tonemap: Shader::from_fragment_file(ctx, "shader/tonemap.frag").unwrap(),
 
             image_tx,
+            config,
+            cached_expr,
+            cached_expr_b,
+
+            controller: SimController::new(),
+            last_mouse: None,
+        }
+    }
+
+    /**
+        Resets the simulation to its initial state, using the current config.
+    **/
+    fn reset(&mut self) {
+        let config = self.config.lock().unwrap().clone();
+        self.t = 0;
+        self.particles = (0..config.initial_particles)
+            .map(|n| Particle::new(0, n as u32, &config, (self.cached_expr.1.as_ref(), self.cached_expr_b.1.as_ref())))
+            .collect();
+    }
+
+    /**
+        Reads mouse drag/scroll and keyboard shortcuts, turning them into camera moves
+        (written straight back into the shared config) and controller commands.
+    **/
+    fn handle_input(&mut self, ctx: &mut Context) {
+        let width = tetra::window::get_width(ctx) as f32;
+        let height = tetra::window::get_height(ctx) as f32;
+        let wh = width.min(height);
+
+        let mouse = input::get_mouse_position(ctx);
+        if input::is_mouse_button_down(ctx, MouseButton::Left) {
+            if let Some(last_mouse) = self.last_mouse {
+                let delta = mouse - last_mouse;
+                let mut config = self.config.lock().unwrap();
+                config.dx -= delta.x * 2.0 * config.scale / wh;
+                config.dy -= delta.y * 2.0 * config.scale / wh;
+            }
+            self.last_mouse = Some(mouse);
+        } else {
+            self.last_mouse = None;
+        }
+
+        let wheel = input::get_mouse_wheel_movement(ctx).y;
+        if wheel != 0 {
+            let mut config = self.config.lock().unwrap();
+            config.scale = (config.scale * (1.0 - 0.1 * wheel as f32)).max(0.01);
+        }
+
+        if input::is_key_pressed(ctx, Key::Space) {
+            self.controller.toggle_pause();
+        }
+        if input::is_key_pressed(ctx, Key::Right) {
+            self.controller.step(1);
+        }
+        if input::is_key_pressed(ctx, Key::R) {
+            self.reset();
         }
     }
 
@@ -155,7 +320,16 @@ impl VectorFieldState {
         Concurrently calculates the new particles' positions.
     **/
     fn update_particles(&mut self) {
-        let mut pool = Pool::new(THREADS);
+        let config = self.config.lock().unwrap().clone();
+        if self.cached_expr.0 != config.expression {
+            self.cached_expr = parse_expr(&config.expression);
+        }
+        if self.cached_expr_b.0 != config.expression_b {
+            self.cached_expr_b = parse_expr(&config.expression_b);
+        }
+        let field_expr = (self.cached_expr.1.as_ref(), self.cached_expr_b.1.as_ref());
+
+        let mut pool = Pool::new(config.threads);
 
         let res: Vec<Particle> = Vec::with_capacity(self.particles.len());
         let res = Mutex::new(res);
@@ -163,12 +337,13 @@ impl VectorFieldState {
         pool.scoped(|scope| {
             let res = &res;
             let particles = &self.particles;
-            for n in 0..(particles.len() / TASK_SIZE) {
+            let config = &config;
+            for n in 0..(particles.len() / config.task_size) {
                 let t = self.t;
                 scope.execute(move || { // move [task_buffer, &res, n, o]
-                    let n = n * TASK_SIZE;
-                    let mut task_buffer = Vec::with_capacity(TASK_SIZE);
-                    for o in n..(n+TASK_SIZE) {
+                    let n = n * config.task_size;
+                    let mut task_buffer = Vec::with_capacity(config.task_size);
+                    for o in n..(n+config.task_size) {
                         if o >= particles.len() {
                             break;
                         }
@@ -177,14 +352,24 @@ impl VectorFieldState {
                         particle.updated = true;
                         particle.age += 1.0;
 
-                        for _ in 0..SUBSTEPS {
-                            let mut z = f(t, particle.position);
+                        let step_size = config.epsilon / config.substeps as f32;
+                        for _ in 0..config.substeps {
+                            let mut z = eval_morphed_field(t, particle.position, field_expr.0, field_expr.1, config.loop_frames);
                             z = z / z.norm();
-                            particle.position += z * (EPSILON / SUBSTEPS as f32);
+                            let mut step = z * step_size;
+                            match particle.kind {
+                                ParticleKind::Streamline => {}
+                                ParticleKind::Drift { bias } => step += bias * step_size,
+                                ParticleKind::Diffuse { jitter } => {
+                                    let mut rng = rand::thread_rng();
+                                    step += Complex::new(rng.gen::<f32>() * 2.0 - 1.0, rng.gen::<f32>() * 2.0 - 1.0) * jitter * step_size;
+                                }
+                            }
+                            particle.position += step;
                         }
 
-                        let d = f(t, particle.position).norm_sqr();
-                        if !(particle.age >= particle.lifetime || d >= 4.0 * SCALE * SCALE || d.is_nan()) {
+                        let d = eval_morphed_field(t, particle.position, field_expr.0, field_expr.1, config.loop_frames).norm_sqr();
+                        if !(particle.age >= particle.lifetime || d >= 4.0 * config.scale * config.scale || d.is_nan()) {
                             task_buffer.push(particle);
                         }
                     }
@@ -201,20 +386,23 @@ impl VectorFieldState {
 
         let res = res.into_inner().unwrap();
         self.particles = res;
-        for n in 0..PARTICLES_PER_FRAME {
-            self.particles.push(Particle::new(self.t as u32, n));
+        for n in 0..config.particles_per_frame {
+            self.particles.push(Particle::new(self.t as u32, n, &config, field_expr));
         }
     }
 }
 
 impl State for VectorFieldState {
     fn update(&mut self, ctx: &mut Context) -> tetra::Result {
-        if LOOP_FRAMES > 1 && self.t > 2 * LOOP_FRAMES as usize {
+        self.handle_input(ctx);
+
+        let config = self.config.lock().unwrap().clone();
+        if config.loop_frames > 1 && self.t > 2 * config.loop_frames as usize {
             println!("Rendering done!");
             tetra::window::quit(ctx);
         }
 
-        if !ANIMATION_MODE {
+        if !config.animation_mode && self.controller.should_advance() {
             self.update_particles();
             self.t += 1;
         }
@@ -223,12 +411,13 @@ impl State for VectorFieldState {
     }
 
     fn draw(&mut self, ctx: &mut Context) -> tetra::Result {
-        if ANIMATION_MODE {
+        let config = self.config.lock().unwrap().clone();
+        if config.animation_mode && self.controller.should_advance() {
             self.update_particles();
             self.t += 1;
         }
         if self.circle.is_none() {
-            self.circle = Some(Mesh::circle(ctx, ShapeStyle::Fill, Vec2::new(0.0, 0.0), PARTICLE_SIZE * 0.5)?);
+            self.circle = Some(Mesh::circle(ctx, ShapeStyle::Fill, Vec2::new(0.0, 0.0), config.particle_size * 0.5)?);
         }
         let circle = self.circle.as_ref().unwrap();
         let background = Color::rgb(0.08, 0.085, 0.12);
@@ -255,17 +444,18 @@ impl State for VectorFieldState {
             if !particle.updated {
                 continue;
             }
-            let x = ((particle.position.re - DX) / SCALE / 2.0 + 0.5) * wh as f32 + dx;
-            let y = ((particle.position.im - DY) / SCALE / 2.0 + 0.5) * wh as f32 + dy;
-            let old_x = ((particle.old_position.re - DX) / SCALE / 2.0 + 0.5) * wh as f32 + dx;
-            let old_y = ((particle.old_position.im - DY) / SCALE / 2.0 + 0.5) * wh as f32 + dy;
+            let x = ((particle.position.re - config.dx) / config.scale / 2.0 + 0.5) * wh as f32 + dx;
+            let y = ((particle.position.im - config.dy) / config.scale / 2.0 + 0.5) * wh as f32 + dy;
+            let old_x = ((particle.old_position.re - config.dx) / config.scale / 2.0 + 0.5) * wh as f32 + dx;
+            let old_y = ((particle.old_position.im - config.dy) / config.scale / 2.0 + 0.5) * wh as f32 + dy;
             particle.old_position = particle.position;
 
-            let alpha = sigmoid(particle.age / PARTICLE_FADE_IN) * sigmoid((particle.lifetime - particle.age) / PARTICLE_FADE_OUT);
+            let alpha = sigmoid(particle.age / config.particle_fade_in) * sigmoid((particle.lifetime - particle.age) / config.particle_fade_out);
+            let color = sample_ramp(&particle.ramp, particle.age / particle.lifetime);
 
-            if ROUND_PARTICLES {
+            if config.round_particles {
                 let mut params: DrawParams = Vec2::new(x, y).into();
-                params.color = particle.color.with_alpha(alpha);
+                params.color = color.with_alpha(alpha);
                 circle.draw(ctx, params.clone());
                 params.position = Vec2::new(old_x, old_y);
                 circle.draw(ctx, params);
@@ -273,8 +463,8 @@ impl State for VectorFieldState {
 
             let line = [Vec2::new(x, y), Vec2::new(old_x, old_y)];
 
-            builder.set_color(particle.color.with_alpha(alpha));
-            builder.polyline(PARTICLE_SIZE, &line)?;
+            builder.set_color(color.with_alpha(alpha));
+            builder.polyline(config.particle_size, &line)?;
         }
 
         let mesh = builder.build_mesh(ctx)?;
@@ -282,9 +472,9 @@ impl State for VectorFieldState {
         mesh.draw(ctx, Vec2::new(0.0, 0.0));
         graphics::reset_canvas(ctx);
 
-        // Bloom filter, using only 3 frag shaders
+        // Bloom filter
         graphics::set_shader(ctx, &self.shader_bloom);
-        self.shader_bloom.set_uniform(ctx, "u_threshold", 0.3);
+        self.shader_bloom.set_uniform(ctx, "u_threshold", config.bloom_threshold);
         graphics::set_canvas(ctx, &self.canvas_bloom);
 
         self.canvas.draw(ctx, Vec2::zero());
@@ -292,33 +482,52 @@ impl State for VectorFieldState {
         graphics::reset_canvas(ctx);
         graphics::reset_shader(ctx);
 
-        graphics::set_canvas(ctx, &self.canvas_blur);
+        // Ping-pong the blur between canvas_blur and canvas_bloom for `bloom_passes` rounds, for a wider glow
         graphics::set_shader(ctx, &self.shader_blur);
-        self.shader_blur.set_uniform(ctx, "u_stepsize", Vec2::new(1.0 / WIDTH as f32, 1.0 / HEIGHT as f32));
-        self.shader_blur.set_uniform(ctx, "u_horizontal", 1i32);
-        self.canvas_bloom.draw(ctx, Vec2::zero());
-
-        graphics::reset_canvas(ctx);
-        graphics::set_canvas(ctx, &self.canvas_bloom);
-        self.shader_blur.set_uniform(ctx, "u_horizontal", 0i32);
-        self.canvas_blur.draw(ctx, Vec2::zero());
+        let stepsize = Vec2::new(
+            config.bloom_blur_radius / config.width as f32,
+            config.bloom_blur_radius / config.height as f32,
+        );
+        self.shader_blur.set_uniform(ctx, "u_stepsize", stepsize);
+        for _ in 0..config.bloom_passes.max(1) {
+            graphics::reset_canvas(ctx);
+            graphics::set_canvas(ctx, &self.canvas_blur);
+            self.shader_blur.set_uniform(ctx, "u_horizontal", 1i32);
+            self.canvas_bloom.draw(ctx, Vec2::zero());
+
+            graphics::reset_canvas(ctx);
+            graphics::set_canvas(ctx, &self.canvas_bloom);
+            self.shader_blur.set_uniform(ctx, "u_horizontal", 0i32);
+            self.canvas_blur.draw(ctx, Vec2::zero());
+            // canvas_bloom stays the active canvas here: either the next pass resets it,
+            // or the additive composite right after the loop draws straight into it.
+        }
 
         graphics::reset_shader(ctx);
         graphics::set_blend_mode(ctx, graphics::BlendMode::Add(graphics::BlendAlphaMode::Multiply));
         self.canvas.draw(ctx, Vec2::zero());
         graphics::reset_canvas(ctx);
         graphics::set_blend_mode(ctx, graphics::BlendMode::Alpha(graphics::BlendAlphaMode::Multiply));
+
+        // Tonemap the composited, high-dynamic-range bloom so dense regions don't just clip to white
+        graphics::set_shader(ctx, &self.shader_tonemap);
+        self.shader_tonemap.set_uniform(ctx, "u_exposure", config.bloom_intensity);
+        graphics::set_canvas(ctx, &self.canvas_tonemap);
         self.canvas_bloom.draw(ctx, Vec2::zero());
+        graphics::reset_canvas(ctx);
+        graphics::reset_shader(ctx);
+
+        self.canvas_tonemap.draw(ctx, Vec2::zero());
 
-        let image_data = self.canvas_bloom.get_data(ctx);
+        let image_data = self.canvas_tonemap.get_data(ctx);
 
-        if SAVING {
-            if LOOP_FRAMES <= 1 {
+        if config.saving {
+            if config.loop_frames <= 1 {
                 // Print every frame
                 self.image_tx.send(image_data).unwrap();
             } else {
                 // Only print [LOOP_FRAMES; 2*LOOP_FRAMES[, exit after that
-                if self.t >= LOOP_FRAMES as usize && self.t < 2 * LOOP_FRAMES as usize {
+                if self.t >= config.loop_frames as usize && self.t < 2 * config.loop_frames as usize {
                     self.image_tx.send(image_data).unwrap();
                 }
             }
@@ -329,9 +538,18 @@ impl State for VectorFieldState {
 }
 
 fn main() -> tetra::Result {
+    let config_path = std::env::args().nth(1).unwrap_or_else(|| "vector-fields.toml".to_string());
+    let config = Arc::new(Mutex::new(Config::load_from_file(&config_path)));
+    config::watch(config_path, config.clone());
+
     let (tx, rx): (Sender<ImageData>, Receiver<ImageData>) = mpsc::channel();
 
-    if SAVING {
+    let (width, height, saving) = {
+        let config = config.lock().unwrap();
+        (config.width, config.height, config.saving)
+    };
+
+    if saving {
         thread::spawn(move || {
             let mut n: usize = 0;
             for image_data in rx {
@@ -344,5 +562,5 @@ fn main() -> tetra::Result {
             }
         });
     }
-    ContextBuilder::new("Vector Fields", WIDTH as i32, HEIGHT as i32).build()?.run(|ctx| Ok(VectorFieldState::new(ctx, tx)))
+    ContextBuilder::new("Vector Fields", width as i32, height as i32).build()?.run(|ctx| Ok(VectorFieldState::new(ctx, tx, config)))
 }